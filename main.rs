@@ -1,12 +1,23 @@
-use chrono::NaiveDate;
-use clap::{ArgGroup, Parser, PossibleValue, Subcommand};
+use chrono::{DateTime, NaiveDate};
+use clap::builder::EnumValueParser;
+#[cfg(feature = "shell")]
+use clap::CommandFactory;
+use clap::{ArgGroup, Parser, PossibleValue, Subcommand, ValueEnum};
 
 use rand::seq::SliceRandom;
 use rand::thread_rng;
+#[cfg(feature = "shell")]
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor, Helper,
+};
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::fs::OpenOptions;
-use std::io::{Read, Write};
+use std::io::{BufRead, ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const SECONDS_IN_DAY: u64 = 86400;
@@ -14,23 +25,46 @@ const CONFIG_FILENAME: &str = ".config/countdown/countdown.toml";
 const ARG_ORDER_SHUFFLE: &str = "shuffle";
 const ARG_ORDER_TIME_DESC: &str = "time-desc";
 const ARG_ORDER_TIME_ASC: &str = "time-asc";
+const OUTPUT_TEXT: &str = "text";
+const OUTPUT_JSON: &str = "json";
+const FORMATTER_PLUGIN_PREFIX: &str = "countdown-format-";
+// Same cut off clap uses for its own "did you mean" hints.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+const WATCH_FILENAME: &str = "watch.json";
+const EXIT_OK: i32 = 0;
+const EXIT_ERROR: i32 = 2;
+const ALERT_EXIT_OK: i32 = 0;
+const ALERT_EXIT_DUE: i32 = 1;
+const FREEFORM_DATE_FORMATS: &[&str] = &["%d-%m-%Y", "%Y-%m-%d", "%d/%m/%Y"];
+const SNAPSHOT_DIRNAME: &str = "snapshots";
+const SNAPSHOT_INDEX: &str = "index";
+const SNAPSHOT_RETENTION_DAYS: u64 = 365;
+const FEDERATION_FILENAME: &str = "federate.toml";
+const PERSONAL_SOURCE: &str = "personal";
+#[cfg(feature = "shell")]
+const SHELL_PROMPT: &str = "countdown> ";
+#[cfg(feature = "shell")]
+const SHELL_EXIT_COMMANDS: &[&str] = &["exit", "quit"];
+const TENTATIVE_MARKER: &str = " (tentative)";
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
 struct CountdownConfig {
     events: Vec<Event>,
 }
 
-impl Default for CountdownConfig {
-    fn default() -> Self {
-        Self { events: Vec::new() }
-    }
-}
-
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 struct Event {
     name: String,
     // Unix timestamp (seconds)
     time: u32,
+    // Unix timestamp (seconds) before which the event is hidden from the
+    // default listing. Doesn't affect `time`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    defer_until: Option<u32>,
+    // Date isn't locked in yet. Shown with a marker and left out of alerts
+    // until confirmed.
+    #[serde(default, skip_serializing_if = "is_false")]
+    tentative: bool,
 }
 
 impl Event {
@@ -45,19 +79,46 @@ impl Event {
         self.days_left(current_time).map(|days| FutureEvent {
             name: self.name.clone(),
             days_left: days,
+            tentative: self.tentative,
         })
     }
 
     fn system_time(&self) -> SystemTime {
         UNIX_EPOCH + Duration::from_secs(self.time.into())
     }
+
+    fn is_deferred(&self, current_time: SystemTime) -> bool {
+        self.defer_until
+            .map(|until| UNIX_EPOCH + Duration::from_secs(until.into()) > current_time)
+            .unwrap_or(false)
+    }
 }
 
 // Validated event that has definitely not occurred yet.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
 struct FutureEvent {
     name: String,
     days_left: u16,
+    #[serde(skip_serializing_if = "is_false")]
+    tentative: bool,
+}
+
+impl FutureEvent {
+    fn label(&self) -> String {
+        tentative_label(&self.name, self.tentative)
+    }
+}
+
+// The name as shown to people, marked if the date isn't locked in yet.
+fn tentative_label(name: &str, tentative: bool) -> String {
+    match tentative {
+        true => format!("{}{}", name, TENTATIVE_MARKER),
+        false => name.to_string(),
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 // CLI
@@ -82,6 +143,20 @@ impl std::str::FromStr for SortOrder {
     }
 }
 
+impl ValueEnum for SortOrder {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Shuffle, Self::TimeAsc, Self::TimeDesc]
+    }
+
+    fn to_possible_value<'a>(&self) -> Option<PossibleValue<'a>> {
+        Some(PossibleValue::new(match self {
+            Self::Shuffle => ARG_ORDER_SHUFFLE,
+            Self::TimeAsc => ARG_ORDER_TIME_ASC,
+            Self::TimeDesc => ARG_ORDER_TIME_DESC,
+        }))
+    }
+}
+
 #[derive(Subcommand, Debug)]
 #[clap(group(
   ArgGroup::new("subcommand")
@@ -98,32 +173,143 @@ enum ESubCommands {
         /// Date of event in <dd>-<mm>-<yyyy> ex: 21-3-2133
         #[clap(short, long = "date")]
         date: String,
+
+        /// The date isn't locked in yet. It's marked and left out of alerts until confirmed
+        #[clap(long)]
+        tentative: bool,
+    },
+
+    /// Mark a tentative event's date as locked in
+    Confirm {
+        /// Name of event
+        event: String,
+    },
+
+    /// Hide an event from the list until a date, without changing its date
+    Defer {
+        /// Name of event
+        event: String,
+
+        /// Date to show the event again in <dd>-<mm>-<yyyy> ex: 1-2-2025
+        #[clap(short, long = "until")]
+        until: String,
+    },
+
+    /// Exit non-zero if events are due soon, for monitoring scripts
+    Alert {
+        /// Number of days to look ahead ex: 14d
+        #[clap(short, long = "within")]
+        within: String,
+
+        /// Print the due events as JSON
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Export upcoming events as a [{label, target_iso}] JSON array for watch faces
+    ExportWatch {
+        /// Max number of events to export
+        #[clap(short, long)]
+        n: Option<usize>,
+
+        /// Write to watch.json next to the events file instead of printing
+        #[clap(short, long)]
+        write: bool,
+    },
+
+    /// Preview anonymized usage stats about your events. Nothing is sent anywhere
+    UsageReport,
+
+    /// Import events from other sources
+    Import {
+        /// Text file to scan for dates, asking about each one found
+        #[clap(long)]
+        freeform: String,
+    },
+
+    /// Run a listing, alert or usage-report against the store as it was on a past date
+    #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+    At {
+        /// Date to look back to in <dd>-<mm>-<yyyy> ex: 1-11-2024
+        date: String,
+
+        /// Options or subcommand to run, as you would pass them to countdown
+        #[clap(multiple_values = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// List events from your store and the other stores in federate.toml, labeled by source
+    Federate {
+        /// Include a source that is disabled in federate.toml
+        #[clap(long, multiple_occurrences = true)]
+        enable: Vec<String>,
+
+        /// Leave out a source, including your own store as "personal"
+        #[clap(long, multiple_occurrences = true)]
+        disable: Vec<String>,
+    },
+
+    /// Rewrite the store sorted by date, dropping duplicates and deferrals that have passed
+    Compact,
+
+    /// Check the store, snapshots, clock and federated sources, suggesting fixes
+    Doctor,
+
+    /// Run commands interactively against one store, saved when you exit
+    Shell,
+
+    /// Run one command per line from a file, saving only if every command succeeds
+    Batch {
+        /// File to read commands from, or - for stdin
+        file: String,
     },
 }
 
+impl ESubCommands {
+    // Whether the command may write the store or files next to it.
+    fn writes(&self) -> bool {
+        match self {
+            Self::AddEvent { .. } | Self::Defer { .. } | Self::Import { .. } => true,
+            Self::Confirm { .. } => true,
+            Self::Compact => true,
+            Self::Shell | Self::Batch { .. } => true,
+            Self::ExportWatch { write, .. } => *write,
+            Self::Doctor => false,
+            Self::Alert { .. } | Self::UsageReport | Self::At { .. } | Self::Federate { .. } => {
+                false
+            }
+        }
+    }
+}
+
 /// Countdown to events you're looking forward to
-#[derive(Parser)]
+#[derive(Parser, Debug)]
 #[clap(author, version, about)]
 #[clap(group(
   ArgGroup::new("options")
       .required(false)
+      .multiple(true)
       .conflicts_with("subcommand")
       // .args(&["set-ver", "major", "minor", "patch"]),
 ))]
 struct CountdownArgs {
     /// Specify the ordering of the events returned
     #[clap(short, long, multiple_values(false), group= "options",
-      value_parser([
-      PossibleValue::new(ARG_ORDER_SHUFFLE),
-      PossibleValue::new(ARG_ORDER_TIME_ASC),
-      PossibleValue::new(ARG_ORDER_TIME_DESC),
-      ]))]
+      value_parser = EnumValueParser::<SortOrder>::new())]
     order: Option<SortOrder>,
 
     /// Max number of events to display.
     #[clap(short, long, multiple_values(false), group = "options")]
     n: Option<usize>,
 
+    /// Output format: text (the default), json, or <name> for a countdown-format-<name> plugin on PATH
+    #[clap(long, multiple_values(false), group = "options")]
+    output: Option<String>,
+
+    /// Read events only from this file. It's never written to and your home directory isn't used
+    #[clap(long, global = true, value_name = "PATH")]
+    events_file: Option<PathBuf>,
+
     #[clap(subcommand)]
     sub: Option<ESubCommands>,
 }
@@ -133,76 +319,335 @@ fn main() {
 
     let cli_matches = CountdownArgs::parse();
 
-    let config_file: Result<PathBuf, String> = dirs::home_dir()
-        .ok_or_else(|| "Failed to find home".to_string())
-        .map(|home| home.join(Path::new(CONFIG_FILENAME)));
+    let config_file: Result<PathBuf, String> =
+        check_listing_options(&cli_matches).and_then(|_| match &cli_matches.events_file {
+            Some(events_file) => events_file_config(events_file, &cli_matches.sub)
+                .and_then(|path| check_strict_schema(&path).map(|_| path)),
+            None => dirs::home_dir()
+                .ok_or_else(|| "Failed to find home".to_string())
+                .map(|home| home.join(Path::new(CONFIG_FILENAME))),
+        });
 
     match config_file {
         Ok(config_file) => {
-            // If its Subcommand
-            if let Some(ESubCommands::AddEvent { event, date }) = &cli_matches.sub {
-                // Validate date
-                // validate with chrono from string
-                match NaiveDate::parse_from_str(date, "%d-%m-%Y") {
-                    Ok(n_date) => {
-                        let unix_time = n_date.and_hms(0, 0, 0).timestamp();
-                        let add_event = CountdownConfig {
-                            events: vec![Event {
-                                name: event.to_owned(),
-                                time: unix_time as u32,
-                            }],
-                        };
-                        match write_configs(&config_file, add_event) {
-                            Ok(_) => println!("Added!"),
-                            Err(s) => println!("{}", s),
-                        }
-                    }
-                    Err(_) => {
-                        eprintln!("Date string in wrong format");
-                    }
+            let code = match &cli_matches.sub {
+                Some(ESubCommands::Shell) => run_shell(now, &config_file),
+                Some(ESubCommands::Batch { file }) => run_batch(now, file, &config_file),
+                Some(ESubCommands::Doctor) => {
+                    run_doctor(now, &config_file, cli_matches.events_file.is_some())
+                }
+                _ => run_once(now, &cli_matches, &config_file),
+            };
+            std::process::exit(code);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+// -o, -n and --output shape the listing. Subcommands have their own options,
+// so these are refused alongside one instead of being silently ignored.
+fn check_listing_options(args: &CountdownArgs) -> Result<(), String> {
+    let listing_options = args.order.is_some() || args.n.is_some() || args.output.is_some();
+
+    match args.sub.is_some() && listing_options {
+        true => Err(String::from(
+            "-o, -n and --output only apply to the listing, not to subcommands",
+        )),
+        false => Ok(()),
+    }
+}
+
+// An events file is a complete, read-only source of truth, as when it's
+// managed declaratively. It has to exist and can't be written to.
+fn events_file_config(events_file: &Path, sub: &Option<ESubCommands>) -> Result<PathBuf, String> {
+    if !events_file.is_file() {
+        Err(format!("{} is not a file", events_file.display()))
+    } else if sub.as_ref().is_some_and(|sub| sub.writes()) {
+        Err(format!(
+            "{} is read-only, so this command can't be used with --events-file",
+            events_file.display()
+        ))
+    } else {
+        Ok(events_file.to_path_buf())
+    }
+}
+
+// The store's schema with unknown keys refused. Only an --events-file is
+// checked against it: a hand-written file should fail on a typo, while the
+// home store and its snapshots stay readable across versions.
+#[derive(serde::Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct StrictConfig {
+    #[allow(dead_code)]
+    events: Vec<StrictEvent>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+#[allow(dead_code)]
+struct StrictEvent {
+    name: String,
+    time: u32,
+    #[serde(default)]
+    defer_until: Option<u32>,
+    #[serde(default)]
+    tentative: bool,
+}
+
+fn check_strict_schema(events_file: &Path) -> Result<(), String> {
+    let buf = std::fs::read_to_string(events_file).map_err(|e| format!("File | Error {}", e))?;
+
+    match buf.trim().is_empty() {
+        true => Ok(()),
+        false => toml::from_str::<StrictConfig>(&buf)
+            .map(|_| ())
+            .map_err(|te| format!("{}: {}", events_file.display(), te)),
+    }
+}
+
+// What running a command did to the in-memory store.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Unchanged,
+    Changed,
+    // The command picked its own exit code, like `alert`.
+    Exit(i32),
+}
+
+// Loads the store, runs a single command and saves the store if it changed.
+fn run_once(now: SystemTime, args: &CountdownArgs, config_file: &Path) -> i32 {
+    let result = read_configs(config_file).and_then(|mut config| {
+        run_command(now, args, config_file, &mut config).and_then(|outcome| match outcome {
+            Outcome::Unchanged => Ok(EXIT_OK),
+            Outcome::Changed => save_configs(config_file, &config).map(|_| EXIT_OK),
+            Outcome::Exit(code) => Ok(code),
+        })
+    });
+
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn run_command(
+    now: SystemTime,
+    args: &CountdownArgs,
+    config_file: &Path,
+    config: &mut CountdownConfig,
+) -> Result<Outcome, String> {
+    match &args.sub {
+        Some(ESubCommands::AddEvent {
+            event,
+            date,
+            tentative,
+        }) => parse_date(date).map(|unix_time| {
+            config.events.push(Event {
+                name: event.to_owned(),
+                time: unix_time,
+                defer_until: None,
+                tentative: *tentative,
+            });
+            println!("Added!");
+
+            Outcome::Changed
+        }),
+        Some(ESubCommands::Defer { event, until }) => parse_date(until).and_then(|until| {
+            match defer_event(&mut config.events, event, until) {
+                0 => Err(no_event_named(event, &config.events)),
+                _ => {
+                    println!("Deferred!");
+                    Ok(Outcome::Changed)
+                }
+            }
+        }),
+        Some(ESubCommands::Confirm { event }) => match confirm_event(&mut config.events, event) {
+            0 => Err(no_event_named(event, &config.events)),
+            _ => {
+                println!("Confirmed!");
+                Ok(Outcome::Changed)
+            }
+        },
+        Some(ESubCommands::Alert { within, json }) => {
+            // An empty store has nothing due, rather than being an error to page on.
+            let report = parse_days(within).map(|days| AlertReport {
+                within_days: days,
+                events: events_due_within(now, &config.events, days),
+            })?;
+
+            if *json {
+                let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+                println!("{}", json);
+            } else {
+                report
+                    .events
+                    .iter()
+                    .for_each(|ev| println!("{} days until {}", ev.days_left, ev.name));
+            }
+
+            match report.events.is_empty() {
+                true => Ok(Outcome::Exit(ALERT_EXIT_OK)),
+                false => Ok(Outcome::Exit(ALERT_EXIT_DUE)),
+            }
+        }
+        Some(ESubCommands::ExportWatch { n, write }) => {
+            // An empty store is an empty feed, so the widget is still refreshed.
+            let entries = watch_entries(now, config.events.clone(), *n);
+            let json = serde_json::to_string(&entries).map_err(|e| e.to_string())?;
+
+            if *write {
+                let watch_file = config_file.with_file_name(WATCH_FILENAME);
+                if let Some(dir) = watch_file.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
                 }
+                std::fs::write(&watch_file, json).map_err(|e| e.to_string())?;
+                println!("Exported to {}", watch_file.display());
             } else {
-                let result = read_configs(&config_file)
-                    .and_then(|s| Ok(applicable_events(now, s.events, &cli_matches)));
-
-                match result {
-                    Ok(events) => events
-                        .iter()
-                        .for_each(|ev| println!("{} days until {}", ev.days_left, ev.name)),
-                    Err(e) => eprintln!("{:?}", e),
+                println!("{}", json);
+            }
+
+            Ok(Outcome::Unchanged)
+        }
+        Some(ESubCommands::UsageReport) => {
+            // Measured from the in-memory store so the shell reports unsaved edits too.
+            let store_bytes = match config.events.is_empty() {
+                true => 0,
+                false => toml::to_string_pretty(config).map_or(0, |toml| toml.len() as u64),
+            };
+            print!("{}", usage_report(now, &config.events, store_bytes));
+
+            Ok(Outcome::Unchanged)
+        }
+        Some(ESubCommands::Import { freeform }) => {
+            let text =
+                std::fs::read_to_string(freeform).map_err(|e| format!("File | Error {}", e))?;
+            let candidates = freeform_candidates(&text);
+            if candidates.is_empty() {
+                println!("No dates found in {}", freeform);
+                return Ok(Outcome::Unchanged);
+            }
+
+            let accepted = choose_candidates(candidates, &mut std::io::stdin().lock())?;
+            println!("Imported {} events", accepted.len());
+
+            match accepted.is_empty() {
+                true => Ok(Outcome::Unchanged),
+                false => {
+                    config.events.extend(accepted);
+                    Ok(Outcome::Changed)
                 }
             }
         }
-        Err(e) => eprintln!("{}", e),
-    }
-}
+        Some(ESubCommands::At { date, command }) => {
+            let time = parse_date(date)?;
+            let args = parse_command_words(command)?;
+
+            match args.sub {
+                None | Some(ESubCommands::Alert { .. }) | Some(ESubCommands::UsageReport) => {
+                    // The store as it was by the end of that day, counted down from its start.
+                    let mut snapshot =
+                        read_snapshot(config_file, u64::from(time) + SECONDS_IN_DAY)?;
+                    run_command(
+                        UNIX_EPOCH + Duration::from_secs(time.into()),
+                        &args,
+                        config_file,
+                        &mut snapshot,
+                    )
+                }
+                _ => Err(String::from(
+                    "Only listing, alert and usage-report can be run at a past date",
+                )),
+            }
+        }
+        Some(ESubCommands::Federate { enable, disable }) => {
+            let federation = read_federation(&config_file.with_file_name(FEDERATION_FILENAME))?;
+            let sources = enabled_sources(&federation.sources, enable, disable)?;
 
-fn write_configs(config_file: &PathBuf, event: CountdownConfig) -> Result<(), String> {
-    let result = match toml::to_string_pretty(&event) {
-        Ok(pretty_toml) => {
-            let file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(true)
-                .open(config_file);
+            let mut labeled = Vec::new();
+            for source in sources {
+                let events = match source.name.as_str() {
+                    PERSONAL_SOURCE => config.events.clone(),
+                    _ => read_source(&source)?,
+                };
+                labeled.push((source.name, events));
+            }
 
-            let result: Result<(), String> = file
-                .map_err(|e| String::from(e.to_string()))
-                .and_then(|mut file| {
-                    file.write(&pretty_toml.as_bytes())
-                        .map_err(|_| String::from(""))
-                        .and_then(|_| Ok(()))
+            federated_events(now, labeled)
+                .iter()
+                .for_each(|(source, ev)| {
+                    println!("{} days until {} ({})", ev.days_left, ev.label(), source)
                 });
 
-            result
+            Ok(Outcome::Unchanged)
         }
-        _ => Err(String::from("parsing to toml string failed")),
-    };
-    result
+        Some(ESubCommands::Compact) => {
+            require_events(config)?;
+            let before = toml::to_string_pretty(config).map_or(0, |toml| toml.len());
+            let count = config.events.len();
+            config.events = compact_events(now, std::mem::take(&mut config.events));
+            let after = toml::to_string_pretty(config).map_or(0, |toml| toml.len());
+
+            println!(
+                "Compacted {} events to {}, {} bytes to {} ({} saved)",
+                count,
+                config.events.len(),
+                before,
+                after,
+                before.saturating_sub(after)
+            );
+
+            // Always rewritten, so hand edits are normalized too.
+            Ok(Outcome::Changed)
+        }
+        Some(ESubCommands::Shell)
+        | Some(ESubCommands::Batch { .. })
+        | Some(ESubCommands::Doctor) => Err(String::from(
+            "shell, batch and doctor can't be run from inside a shell or batch",
+        )),
+        None => {
+            let events = require_events(config)
+                .map(|events| applicable_events(now, events.to_vec(), args))?;
+            let output = args.output.as_deref().unwrap_or(OUTPUT_TEXT);
+            print!("{}", format_events(output, &events)?);
+
+            Ok(Outcome::Unchanged)
+        }
+    }
+}
+
+// Rewrites the whole store. The new store is written next to the old one and
+// renamed over it, so a failed write never leaves a half written store.
+fn save_configs(config_file: &Path, config: &CountdownConfig) -> Result<(), String> {
+    if let Some(dir) = config_file.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let pretty_toml = toml::to_string_pretty(config)
+        .map_err(|_| String::from("parsing to toml string failed"))?;
+    let tmp_file = config_file.with_extension("toml.tmp");
+
+    std::fs::write(&tmp_file, &pretty_toml)
+        .and_then(|_| std::fs::rename(&tmp_file, config_file))
+        .map_err(|e| e.to_string())?;
+
+    // The store is saved by now, so a failed snapshot only loses history.
+    if let Err(e) = snapshot_store(config_file, &pretty_toml, SystemTime::now()) {
+        eprintln!("Saved, but no snapshot was taken: {}", e);
+    }
+
+    Ok(())
 }
 
-fn read_configs(config_file: &PathBuf) -> Result<CountdownConfig, String> {
-    if Path::new(config_file).exists() {
+// Reads the store. A missing or empty file is an empty store, so that the
+// first `add-event` can create it.
+fn read_configs(config_file: &Path) -> Result<CountdownConfig, String> {
+    if config_file.exists() {
         let mut buf = String::new();
 
         let file = OpenOptions::new().read(true).open(config_file);
@@ -210,163 +655,1800 @@ fn read_configs(config_file: &PathBuf) -> Result<CountdownConfig, String> {
         let result: Result<CountdownConfig, String> = match file {
             Ok(mut file) => file
                 .read_to_string(&mut buf)
-                .map_err(|e| String::from(e.to_string()))
+                .map_err(|e| e.to_string())
                 .and_then(|_| {
-                    if buf.is_empty() {
-                        Err(String::from("No Entires"))
+                    if buf.trim().is_empty() {
+                        Ok(CountdownConfig::default())
                     } else {
                         toml::from_str::<CountdownConfig>(&buf)
-                            .map_err(|te| String::from(te.to_string()))
+                            .map_err(|te| format!("{}: {}", config_file.display(), te))
                     }
                 }),
-            Err(e) => Err(format!("File | Error {}", e.to_string())),
+            Err(e) => Err(format!("File | Error {}", e)),
         };
 
         result
     } else {
+        Ok(CountdownConfig::default())
+    }
+}
+
+fn require_events(config: &CountdownConfig) -> Result<&[Event], String> {
+    if config.events.is_empty() {
         Err(String::from("No Entires!. Please add events first"))
+    } else {
+        Ok(&config.events)
     }
 }
 
-fn filter_expired_events(now: SystemTime, events: &Vec<Event>) -> Vec<FutureEvent> {
-    events
-        .iter()
-        .filter_map(|ev| ev.as_future_event(now))
+// Freeform import
+
+// Finds lines containing a date, using the rest of the line as the name.
+// Only four digit years count, so "5-6-30" in a note isn't read as year 30.
+fn freeform_candidates(text: &str) -> Vec<Event> {
+    text.lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            words.iter().enumerate().find_map(|(i, word)| {
+                let word = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+                if !word.split(['-', '/']).any(|part| part.len() == 4) {
+                    return None;
+                }
+                let date = FREEFORM_DATE_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDate::parse_from_str(word, format).ok())?;
+
+                let name = [&words[..i], &words[i + 1..]].concat().join(" ");
+                let name = name.trim_matches(|c: char| c.is_whitespace() || "-*:,;".contains(c));
+
+                match name.is_empty() {
+                    true => None,
+                    false => Some(Event {
+                        name: name.to_string(),
+                        time: date_timestamp(date)?,
+                        defer_until: None,
+                        tentative: false,
+                    }),
+                }
+            })
+        })
         .collect()
 }
 
-fn events_sorted_by_time(events: &Vec<FutureEvent>, is_asc: bool) -> Vec<FutureEvent> {
-    let mut cloned_events = events.clone();
-    cloned_events.sort_by(|a, b| {
-        if is_asc {
-            a.days_left.cmp(&b.days_left)
-        } else {
-            b.days_left.cmp(&a.days_left)
+// Asks about each candidate: y adds it, a adds it and all the rest, q stops
+// and anything else skips it.
+fn choose_candidates<R: BufRead>(
+    candidates: Vec<Event>,
+    input: &mut R,
+) -> Result<Vec<Event>, String> {
+    let mut accepted = Vec::new();
+    let mut accept_all = false;
+
+    for candidate in candidates {
+        if !accept_all {
+            print!(
+                "Add '{}' on {}? [y/n/a/q] ",
+                candidate.name,
+                format_timestamp(candidate.time.into(), "%d-%m-%Y")
+            );
+            std::io::stdout().flush().map_err(|e| e.to_string())?;
+
+            let mut answer = String::new();
+            input.read_line(&mut answer).map_err(|e| e.to_string())?;
+            match answer.trim() {
+                "y" => (),
+                "a" => accept_all = true,
+                "q" => break,
+                _ => continue,
+            }
         }
-    });
 
-    cloned_events
+        accepted.push(candidate);
+    }
+
+    Ok(accepted)
 }
 
-fn sort_events(events: &Vec<FutureEvent>, order: &Option<SortOrder>) -> Vec<FutureEvent> {
-    match order {
-        Some(o) => match o {
-            SortOrder::Shuffle => {
-                let mut cloned = events.clone();
-                cloned.shuffle(&mut thread_rng());
+// Federation
 
-                cloned
-            }
-            SortOrder::TimeAsc => events_sorted_by_time(events, true),
-            SortOrder::TimeDesc => events_sorted_by_time(events, false),
-        },
-        None => events_sorted_by_time(events, true),
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct FederationConfig {
+    #[serde(default)]
+    sources: Vec<Source>,
+}
+
+// Another events file to list alongside your own store.
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Source {
+    name: String,
+    path: PathBuf,
+    #[serde(default = "source_enabled_default")]
+    enabled: bool,
+}
+
+fn source_enabled_default() -> bool {
+    true
+}
+
+fn read_federation(federation_file: &Path) -> Result<FederationConfig, String> {
+    match federation_file.exists() {
+        true => std::fs::read_to_string(federation_file)
+            .map_err(|e| format!("File | Error {}", e))
+            .and_then(|buf| {
+                let dir = federation_file.parent().unwrap_or_else(|| Path::new(""));
+                parse_federation(&buf, dir)
+                    .map_err(|e| format!("{}: {}", federation_file.display(), e))
+            }),
+        false => Ok(FederationConfig::default()),
     }
 }
 
-fn limit_events(events: Vec<FutureEvent>, limit: Option<usize>) -> Vec<FutureEvent> {
-    match limit {
-        Some(n) => events.into_iter().take(n).collect(),
-        None => events,
+// Relative source paths are relative to `dir`, the directory of
+// federate.toml, not to wherever countdown is run from. Names have to be
+// unique, and "personal" is kept for your own store.
+fn parse_federation(buf: &str, dir: &Path) -> Result<FederationConfig, String> {
+    let mut federation: FederationConfig = toml::from_str(buf).map_err(|te| te.to_string())?;
+    let mut names = HashSet::new();
+    for source in federation.sources.iter_mut() {
+        if source.name == PERSONAL_SOURCE {
+            return Err(format!(
+                "source name '{}' is reserved for your own store",
+                PERSONAL_SOURCE
+            ));
+        }
+        if !names.insert(source.name.clone()) {
+            return Err(format!("source '{}' is listed more than once", source.name));
+        }
+        source.path = dir.join(&source.path);
     }
+
+    Ok(federation)
 }
 
-fn applicable_events(
-    now: SystemTime,
-    events: Vec<Event>,
-    args: &CountdownArgs,
-) -> Vec<FutureEvent> {
-    let current = filter_expired_events(now, &events);
-    let sorted = sort_events(&current, &args.order);
+// Unlike your own store, a source file that doesn't exist is an error, as
+// it's most likely a typo in federate.toml.
+fn read_source(source: &Source) -> Result<Vec<Event>, String> {
+    match source.path.is_file() {
+        true => read_configs(&source.path)
+            .map(|config| config.events)
+            .map_err(|e| format!("Source '{}': {}", source.name, e)),
+        false => Err(format!(
+            "Source '{}': {} is not a file",
+            source.name,
+            source.path.display()
+        )),
+    }
+}
 
-    limit_events(sorted, args.n)
+// Your own store comes first as "personal", then the configured sources,
+// with `enable` and `disable` overriding what the config says.
+fn enabled_sources(
+    sources: &[Source],
+    enable: &[String],
+    disable: &[String],
+) -> Result<Vec<Source>, String> {
+    let personal = Source {
+        name: PERSONAL_SOURCE.to_string(),
+        path: PathBuf::new(),
+        enabled: true,
+    };
+    let all: Vec<Source> = std::iter::once(personal)
+        .chain(sources.iter().cloned())
+        .collect();
+
+    if let Some(unknown) = enable
+        .iter()
+        .chain(disable)
+        .find(|name| all.iter().all(|source| &source.name != *name))
+    {
+        return Err(format!(
+            "Unknown source '{}'{}",
+            unknown,
+            did_you_mean(closest_match(
+                unknown,
+                all.iter().map(|source| source.name.as_str())
+            ))
+        ));
+    }
+
+    Ok(all
+        .into_iter()
+        .filter(|source| {
+            !disable.contains(&source.name) && (source.enabled || enable.contains(&source.name))
+        })
+        .collect())
 }
 
-#[cfg(test)]
-mod tests {
+// Upcoming events from every source, soonest first, each with its source.
+fn federated_events(
+    now: SystemTime,
+    sources: Vec<(String, Vec<Event>)>,
+) -> Vec<(String, FutureEvent)> {
+    let mut labeled: Vec<(String, FutureEvent)> = sources
+        .into_iter()
+        .flat_map(|(source, events)| {
+            filter_expired_events(now, &filter_deferred_events(now, events))
+                .into_iter()
+                .map(move |ev| (source.clone(), ev))
+        })
+        .collect();
+    labeled.sort_by_key(|(_, ev)| ev.days_left);
 
-    use std::str::FromStr;
+    labeled
+}
 
-    use chrono::DateTime;
+// Snapshots
 
-    use super::*;
+// FNV-1a, used to name snapshots after their content. Unlike `DefaultHasher`
+// its output is stable across Rust releases.
+fn content_hash(content: &str) -> String {
+    let hash = content
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
 
-    // Event
-    #[test]
-    fn event_days_left_calculates_remaining_days_correctly() {
-        let event = Event {
-            name: "test".to_string(),
-            time: 172800,
-        };
-        let result = event.days_left(UNIX_EPOCH);
+    format!("{:016x}", hash)
+}
 
-        assert_eq!(result, Some(2));
-    }
+// Each index line is `<unix timestamp> <hash>`, in the order they were taken.
+// Returns the hash of the newest snapshot taken at or before `time`. The
+// clock can jump back, so timestamps aren't assumed to be in order.
+fn snapshot_at(index: &str, time: u64) -> Option<&str> {
+    index_entries(index)
+        .filter(|(taken, _)| *taken <= time)
+        .max_by_key(|(taken, _)| *taken)
+        .map(|(_, hash)| hash)
+}
 
-    #[test]
-    fn event_days_left_returns_none_if_expired() {
-        let event = Event {
-            name: "test".to_string(),
-            time: 5000,
-        };
-        let result = event.days_left(UNIX_EPOCH + Duration::from_secs(10000));
+fn index_entries(index: &str) -> impl Iterator<Item = (u64, &str)> {
+    index
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter_map(|(taken, hash)| taken.parse::<u64>().ok().map(|taken| (taken, hash)))
+}
 
-        assert_eq!(result, None);
+// Stores `content` under its hash and records it in the index, unless it's
+// the same as the latest snapshot. Snapshots outside the retention rule are
+// then dropped.
+fn snapshot_store(config_file: &Path, content: &str, now: SystemTime) -> Result<(), String> {
+    let dir = config_file.with_file_name(SNAPSHOT_DIRNAME);
+    let index_file = dir.join(SNAPSHOT_INDEX);
+    let index = std::fs::read_to_string(&index_file).unwrap_or_default();
+
+    let hash = content_hash(content);
+    // Compared with the last one recorded, whatever the clock said then.
+    if index_entries(&index).last().map(|(_, hash)| hash) == Some(hash.as_str()) {
+        return Ok(());
     }
 
-    #[test]
-    fn event_as_future_event_returns_future_event_if_not_expired() {
-        let event = Event {
-            name: "test".to_string(),
-            time: 172800,
-        };
-        let result = event.as_future_event(UNIX_EPOCH);
+    let snapshot_file = dir.join(format!("{}.toml", hash));
+    let taken = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
 
-        assert_eq!(
-            result,
-            Some(FutureEvent {
-                name: "test".to_string(),
-                days_left: 2,
-            })
-        );
-    }
+    let index = format!("{}{} {}\n", index, taken, hash);
+    let retained = retained_entries(&index, taken);
+    let kept: HashSet<&str> = retained.iter().map(|(_, hash)| *hash).collect();
+    let new_index: String = retained
+        .iter()
+        .map(|(taken, hash)| format!("{} {}\n", taken, hash))
+        .collect();
+    let tmp_index = dir.join(format!("{}.tmp", SNAPSHOT_INDEX));
 
-    #[test]
-    fn event_as_future_event_returns_none_if_expired() {
-        let event = Event {
-            name: "test".to_string(),
-            time: 172800,
-        };
-        let result = event.as_future_event(UNIX_EPOCH + Duration::from_secs(172801));
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| match snapshot_file.exists() {
+            true => Ok(()),
+            false => std::fs::write(&snapshot_file, content),
+        })
+        .and_then(|_| std::fs::write(&tmp_index, new_index))
+        .and_then(|_| std::fs::rename(&tmp_index, &index_file))
+        .map_err(|e| e.to_string())?;
 
-        assert_eq!(result, None);
+    // Only files the index named are removed, and only once it no longer does.
+    for (_, dropped) in index_entries(&index).filter(|(_, hash)| !kept.contains(hash)) {
+        let _ = std::fs::remove_file(dir.join(format!("{}.toml", dropped)));
+    }
+
+    Ok(())
+}
+
+// `at` only looks at whole days, so only the last snapshot of each day is
+// kept, for SNAPSHOT_RETENTION_DAYS. The newest snapshot from before that is
+// kept too, as it's the store as it was at the start of the kept days.
+fn retained_entries(index: &str, now: u64) -> Vec<(u64, &str)> {
+    let entries: Vec<(u64, &str)> = index_entries(index).collect();
+    let cutoff = now.saturating_sub(SNAPSHOT_RETENTION_DAYS * SECONDS_IN_DAY);
+
+    let daily: Vec<(u64, &str)> = entries
+        .iter()
+        .enumerate()
+        .filter(|(i, (taken, _))| {
+            entries[i + 1..]
+                .iter()
+                .all(|(later, _)| later / SECONDS_IN_DAY != taken / SECONDS_IN_DAY)
+        })
+        .map(|(_, entry)| *entry)
+        .collect();
+    let newest_expired = daily
+        .iter()
+        .filter(|(taken, _)| *taken < cutoff)
+        .max_by_key(|(taken, _)| *taken)
+        .copied();
+
+    daily
+        .into_iter()
+        .filter(|entry| entry.0 >= cutoff || Some(*entry) == newest_expired)
+        .collect()
+}
+
+fn read_snapshot(config_file: &Path, time: u64) -> Result<CountdownConfig, String> {
+    let dir = config_file.with_file_name(SNAPSHOT_DIRNAME);
+    let index = std::fs::read_to_string(dir.join(SNAPSHOT_INDEX)).unwrap_or_default();
+
+    match snapshot_at(&index, time) {
+        Some(hash) => read_configs(&dir.join(format!("{}.toml", hash))),
+        None => Err(String::from("No snapshot of the store from that date")),
+    }
+}
+
+// Doctor
+
+// What a check found, or the problem and how to fix it.
+type CheckResult = Result<String, (String, String)>;
+
+// Runs every check against the files as they are on disk, without loading the
+// store first, so a broken store is reported rather than stopping the run.
+fn run_doctor(now: SystemTime, config_file: &Path, read_only: bool) -> i32 {
+    let index = std::fs::read_to_string(
+        config_file
+            .with_file_name(SNAPSHOT_DIRNAME)
+            .join(SNAPSHOT_INDEX),
+    )
+    .unwrap_or_default();
+
+    let checks = [
+        ("store", check_store(config_file, read_only)),
+        ("writable", check_writable(config_file, read_only)),
+        ("clock", check_clock(now, &index)),
+        (
+            "federation",
+            check_federation(&config_file.with_file_name(FEDERATION_FILENAME)),
+        ),
+    ];
+
+    let mut healthy = true;
+    for (name, result) in checks.iter() {
+        match result {
+            Ok(found) => println!("ok    {:<12}{}", name, found),
+            Err((problem, fix)) => {
+                healthy = false;
+                println!("FAIL  {:<12}{}", name, problem);
+                println!("{:18}fix: {}", "", fix);
+            }
+        }
+    }
+
+    match healthy {
+        true => EXIT_OK,
+        false => EXIT_ERROR,
+    }
+}
+
+fn check_store(config_file: &Path, read_only: bool) -> CheckResult {
+    if !config_file.exists() {
+        return Ok(format!(
+            "{} doesn't exist yet, add-event creates it",
+            config_file.display()
+        ));
+    }
+
+    read_configs(config_file)
+        .map(|config| format!("{} ({} events)", config_file.display(), config.events.len()))
+        .map_err(|e| match read_only {
+            true => (
+                e,
+                String::from("correct the events file where it's generated"),
+            ),
+            false => (
+                e,
+                String::from("correct the file by hand, or copy an older version from snapshots"),
+            ),
+        })
+}
+
+// Writes and removes a probe file next to the store, the way saving does.
+fn check_writable(config_file: &Path, read_only: bool) -> CheckResult {
+    let dir = match config_file.parent() {
+        Some(dir) if !read_only => dir,
+        _ => return Ok(String::from("read-only, events come from --events-file")),
+    };
+    if !dir.exists() {
+        return Ok(format!("{} will be created on first save", dir.display()));
+    }
+
+    let probe = config_file.with_extension("toml.doctor");
+    std::fs::write(&probe, "")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map(|_| format!("{} can be written to", dir.display()))
+        .map_err(|e| {
+            (
+                format!("can't write to {}: {}", dir.display(), e),
+                String::from("check the owner and permissions of that directory"),
+            )
+        })
+}
+
+// Snapshots are indexed by the time they were taken, so a clock earlier than
+// the newest one means the system time is wrong or has jumped back.
+fn check_clock(now: SystemTime, index: &str) -> CheckResult {
+    let now_secs = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let latest = index_entries(index).map(|(taken, _)| taken).max();
+
+    match latest {
+        Some(latest) if latest > now_secs => Err((
+            format!(
+                "system time is before the newest snapshot, taken {}",
+                format_timestamp(latest as i64, "%d-%m-%Y %H:%M UTC")
+            ),
+            String::from("set the system date and time, or enable time sync"),
+        )),
+        _ => Ok(format_timestamp(now_secs as i64, "%d-%m-%Y %H:%M UTC")),
+    }
+}
+
+fn check_federation(federation_file: &Path) -> CheckResult {
+    let federation = read_federation(federation_file).map_err(|e| {
+        (
+            e,
+            format!("correct or remove {}", federation_file.display()),
+        )
+    })?;
+
+    let unreadable: Vec<String> = federation
+        .sources
+        .iter()
+        .filter(|source| source.enabled && read_source(source).is_err())
+        .map(|source| format!("{} ({})", source.name, source.path.display()))
+        .collect();
+
+    match unreadable.is_empty() {
+        true => Ok(format!("{} sources readable", federation.sources.len())),
+        false => Err((
+            format!("can't read {}", unreadable.join(", ")),
+            format!(
+                "correct the path in {} or set enabled = false",
+                federation_file.display()
+            ),
+        )),
+    }
+}
+
+// Shell
+
+// Reads commands line by line against one in-memory store, saving it once
+// on exit if anything changed.
+#[cfg(feature = "shell")]
+fn run_shell(now: SystemTime, config_file: &Path) -> i32 {
+    let mut config = match read_configs(config_file) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    };
+
+    let mut editor = match Editor::<ShellHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    };
+    editor.set_helper(Some(ShellHelper::new(&config)));
+
+    let mut changed = false;
+    loop {
+        let line = match editor.readline(SHELL_PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                break;
+            }
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if SHELL_EXIT_COMMANDS.contains(&line) {
+            break;
+        }
+
+        match parse_command_line(line)
+            .and_then(|args| run_command(now, &args, config_file, &mut config))
+        {
+            Ok(Outcome::Changed) => {
+                changed = true;
+                if let Some(helper) = editor.helper_mut() {
+                    *helper = ShellHelper::new(&config);
+                }
+            }
+            Ok(_) => (),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if !changed {
+        return EXIT_OK;
+    }
+
+    match save_configs(config_file, &config) {
+        Ok(_) => {
+            println!("Saved!");
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+// The shell needs rustyline, which builds without the `shell` feature leave out.
+#[cfg(not(feature = "shell"))]
+fn run_shell(_now: SystemTime, _config_file: &Path) -> i32 {
+    eprintln!("shell isn't available, as countdown was built without the `shell` feature");
+    EXIT_ERROR
+}
+
+// Batch
+
+// Parses every line before running any, then runs them all against one
+// in-memory store and saves it once. Any failure leaves the store untouched.
+fn run_batch(now: SystemTime, file: &str, config_file: &Path) -> i32 {
+    let result = read_batch(file)
+        .and_then(|input| parse_batch(&input))
+        .and_then(|commands| {
+            let mut config = read_configs(config_file)?;
+            apply_batch(now, &commands, config_file, &mut config)?;
+            save_configs(config_file, &config).map(|_| commands.len())
+        });
+
+    match result {
+        Ok(count) => {
+            println!("Applied {} commands", count);
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Nothing was saved");
+            EXIT_ERROR
+        }
+    }
+}
+
+fn read_batch(file: &str) -> Result<String, String> {
+    let mut input = String::new();
+    let result = match file {
+        "-" => std::io::stdin().read_to_string(&mut input),
+        _ => OpenOptions::new()
+            .read(true)
+            .open(file)
+            .and_then(|mut f| f.read_to_string(&mut input)),
+    };
+
+    result
+        .map(|_| input)
+        .map_err(|e| format!("File | Error {}", e))
+}
+
+// Blank lines and lines starting with `#` are skipped.
+fn parse_batch(input: &str) -> Result<Vec<(usize, CountdownArgs)>, String> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            parse_command_line(line)
+                .map(|args| (number, args))
+                .map_err(|e| format!("Line {}: {}", number, e))
+        })
+        .collect()
+}
+
+fn apply_batch(
+    now: SystemTime,
+    commands: &[(usize, CountdownArgs)],
+    config_file: &Path,
+    config: &mut CountdownConfig,
+) -> Result<(), String> {
+    commands.iter().try_for_each(|(number, args)| {
+        run_command(now, args, config_file, config)
+            .map(|_| ())
+            .map_err(|e| format!("Line {}: {}", number, e))
+    })
+}
+
+fn parse_command_line(line: &str) -> Result<CountdownArgs, String> {
+    shell_words::split(line)
+        .map_err(|e| e.to_string())
+        .and_then(|words| parse_command_words(&words))
+}
+
+fn parse_command_words(words: &[String]) -> Result<CountdownArgs, String> {
+    CountdownArgs::try_parse_from(
+        std::iter::once("countdown").chain(words.iter().map(|w| w.as_str())),
+    )
+    .map_err(|e| e.to_string().trim_end().to_string())
+    .and_then(|args| check_listing_options(&args).map(|_| args))
+    .and_then(|args| match args.events_file {
+        // The store is already chosen by the time a line is run.
+        Some(_) => Err(String::from(
+            "--events-file can only be given to countdown itself, not to a line",
+        )),
+        None => Ok(args),
+    })
+}
+
+// Tab completion of subcommands, their flags and event names.
+#[cfg(feature = "shell")]
+struct ShellHelper {
+    subcommands: Vec<String>,
+    flags: Vec<(String, Vec<String>)>,
+    names: Vec<String>,
+}
+
+#[cfg(feature = "shell")]
+impl ShellHelper {
+    fn new(config: &CountdownConfig) -> Self {
+        let command = CountdownArgs::command();
+        let long_flags = |command: &clap::Command| -> Vec<String> {
+            command
+                .get_arguments()
+                .filter_map(|arg| arg.get_long())
+                .map(|long| format!("--{}", long))
+                .collect()
+        };
+
+        let mut flags = vec![(String::new(), long_flags(&command))];
+        flags.extend(
+            command
+                .get_subcommands()
+                .map(|sub| (sub.get_name().to_string(), long_flags(sub))),
+        );
+
+        Self {
+            subcommands: command
+                .get_subcommands()
+                .map(|sub| sub.get_name().to_string())
+                .chain(SHELL_EXIT_COMMANDS.iter().map(|c| c.to_string()))
+                .collect(),
+            flags,
+            names: config.events.iter().map(|ev| ev.name.clone()).collect(),
+        }
+    }
+
+    fn candidates(&self, line: &str, word: &str) -> Vec<String> {
+        let subcommand = line.split_whitespace().next().unwrap_or("");
+        let first_word = line.trim_start().len() == word.len();
+
+        let flags = self
+            .flags
+            .iter()
+            .find(|(name, _)| name == subcommand)
+            .or_else(|| self.flags.first())
+            .map(|(_, flags)| flags.clone())
+            .unwrap_or_default();
+
+        if word.starts_with('-') {
+            flags.into_iter().filter(|f| f.starts_with(word)).collect()
+        } else if first_word {
+            self.subcommands
+                .iter()
+                .filter(|sub| sub.starts_with(word))
+                .cloned()
+                .collect()
+        } else {
+            // Names are matched unquoted but completed quoted, as they may contain spaces.
+            let prefix = word.trim_start_matches(['\'', '"']);
+            self.names
+                .iter()
+                .filter(|name| name.starts_with(prefix))
+                .map(|name| shell_words::quote(name).into_owned())
+                .collect()
+        }
+    }
+}
+
+#[cfg(feature = "shell")]
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = word_start(&line[..pos]);
+
+        Ok((start, self.candidates(&line[..pos], &line[start..pos])))
+    }
+}
+
+// Byte offset of the word being typed at the end of `line`. Whitespace such
+// as U+00A0 is more than one byte, so the offset is taken past the whole char.
+#[cfg(feature = "shell")]
+fn word_start(line: &str) -> usize {
+    line.char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+#[cfg(feature = "shell")]
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+#[cfg(feature = "shell")]
+impl Highlighter for ShellHelper {}
+
+#[cfg(feature = "shell")]
+impl Validator for ShellHelper {}
+
+#[cfg(feature = "shell")]
+impl Helper for ShellHelper {}
+
+// Parses a <dd>-<mm>-<yyyy> date into a unix timestamp at midnight.
+fn parse_date(date: &str) -> Result<u32, String> {
+    NaiveDate::parse_from_str(date, "%d-%m-%Y")
+        .map_err(|_| String::from("Date string in wrong format"))
+        .and_then(|n_date| {
+            date_timestamp(n_date)
+                .ok_or_else(|| format!("Date {} is outside 1-1-1970 to 7-2-2106", date))
+        })
+}
+
+// Unix timestamp at midnight UTC, if it fits the store's u32 seconds.
+fn date_timestamp(date: NaiveDate) -> Option<u32> {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|time| u32::try_from(time.and_utc().timestamp()).ok())
+}
+
+// Formats a unix timestamp as a UTC date, falling back to the raw number if
+// it's out of chrono's range.
+fn format_timestamp(secs: i64, fmt: &str) -> String {
+    DateTime::from_timestamp(secs, 0)
+        .map_or_else(|| secs.to_string(), |time| time.format(fmt).to_string())
+}
+
+// Defers every event called `name`, returning how many were found.
+fn defer_event(events: &mut [Event], name: &str, until: u32) -> usize {
+    let mut found = 0;
+    for ev in events.iter_mut().filter(|ev| ev.name == name) {
+        ev.defer_until = Some(until);
+        found += 1;
+    }
+
+    found
+}
+
+// Canonical form of the store: sorted by date then name, without exact
+// duplicates or deferrals that have already passed.
+fn compact_events(now: SystemTime, mut events: Vec<Event>) -> Vec<Event> {
+    for ev in events.iter_mut().filter(|ev| !ev.is_deferred(now)) {
+        ev.defer_until = None;
+    }
+    events.sort_by_key(|ev| (ev.time, ev.name.clone(), ev.defer_until, ev.tentative));
+    events.dedup();
+
+    events
+}
+
+// Locks in the date of every event called `name`, returning how many were found.
+fn confirm_event(events: &mut [Event], name: &str) -> usize {
+    let mut found = 0;
+    for ev in events.iter_mut().filter(|ev| ev.name == name) {
+        ev.tentative = false;
+        found += 1;
+    }
+
+    found
+}
+
+fn no_event_named(name: &str, events: &[Event]) -> String {
+    format!(
+        "No event named '{}'{}",
+        name,
+        did_you_mean(closest_match(
+            name,
+            events.iter().map(|ev| ev.name.as_str())
+        ))
+    )
+}
+
+fn filter_deferred_events(now: SystemTime, events: Vec<Event>) -> Vec<Event> {
+    events
+        .into_iter()
+        .filter(|ev| !ev.is_deferred(now))
+        .collect()
+}
+
+fn filter_expired_events(now: SystemTime, events: &[Event]) -> Vec<FutureEvent> {
+    events
+        .iter()
+        .filter_map(|ev| ev.as_future_event(now))
+        .collect()
+}
+
+fn events_sorted_by_time(events: &[FutureEvent], is_asc: bool) -> Vec<FutureEvent> {
+    let mut cloned_events = events.to_vec();
+    cloned_events.sort_by(|a, b| {
+        if is_asc {
+            a.days_left.cmp(&b.days_left)
+        } else {
+            b.days_left.cmp(&a.days_left)
+        }
+    });
+
+    cloned_events
+}
+
+fn sort_events(events: &[FutureEvent], order: &Option<SortOrder>) -> Vec<FutureEvent> {
+    match order {
+        Some(o) => match o {
+            SortOrder::Shuffle => {
+                let mut cloned = events.to_vec();
+                cloned.shuffle(&mut thread_rng());
+
+                cloned
+            }
+            SortOrder::TimeAsc => events_sorted_by_time(events, true),
+            SortOrder::TimeDesc => events_sorted_by_time(events, false),
+        },
+        None => events_sorted_by_time(events, true),
+    }
+}
+
+fn limit_events(events: Vec<FutureEvent>, limit: Option<usize>) -> Vec<FutureEvent> {
+    match limit {
+        Some(n) => events.into_iter().take(n).collect(),
+        None => events,
+    }
+}
+
+// Suggestions
+
+// Closest candidate to a mistyped name, if any is close enough.
+fn closest_match<'a, I>(input: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::jaro(input, candidate), candidate))
+        .filter(|(score, _)| *score > SUGGESTION_THRESHOLD)
+        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))
+        .map(|(_, candidate)| candidate)
+}
+
+fn did_you_mean(suggestion: Option<&str>) -> String {
+    suggestion
+        .map(|s| format!(", did you mean '{}'?", s))
+        .unwrap_or_default()
+}
+
+// Output
+
+type Formatter = fn(&[FutureEvent]) -> Result<String, String>;
+
+const FORMATTERS: &[(&str, Formatter)] = &[(OUTPUT_TEXT, format_text), (OUTPUT_JSON, format_json)];
+
+fn format_text(events: &[FutureEvent]) -> Result<String, String> {
+    Ok(events
+        .iter()
+        .map(|ev| format!("{} days until {}\n", ev.days_left, ev.label()))
+        .collect())
+}
+
+fn format_json(events: &[FutureEvent]) -> Result<String, String> {
+    serde_json::to_string_pretty(events)
+        .map(|json| json + "\n")
+        .map_err(|e| e.to_string())
+}
+
+// Formats with a built in formatter, falling back to a plugin executable
+// which receives the events as JSON on stdin and prints the output.
+fn format_events(output: &str, events: &[FutureEvent]) -> Result<String, String> {
+    match FORMATTERS.iter().find(|(name, _)| *name == output) {
+        Some((_, formatter)) => formatter(events),
+        None => run_formatter_plugin(output, events),
+    }
+}
+
+fn unknown_output(output: &str, plugin: &str) -> String {
+    format!(
+        "Unknown output '{}'{}\nBuilt in outputs are {}, or add a {} executable to PATH",
+        output,
+        did_you_mean(closest_match(
+            output,
+            FORMATTERS.iter().map(|(name, _)| *name)
+        )),
+        FORMATTERS
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(", "),
+        plugin
+    )
+}
+
+fn run_formatter_plugin(output: &str, events: &[FutureEvent]) -> Result<String, String> {
+    let plugin = format!("{}{}", FORMATTER_PLUGIN_PREFIX, output);
+
+    // Anything else, like a `/`, would make Command run a path instead of
+    // looking the plugin up on PATH.
+    let valid_name = !output.is_empty()
+        && output
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        return Err(unknown_output(output, &plugin));
+    }
+
+    let json = serde_json::to_string(events).map_err(|e| e.to_string())?;
+
+    let mut child = Command::new(&plugin)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => unknown_output(output, &plugin),
+            _ => format!("{} | Error {}", plugin, e),
+        })?;
+
+    // Written from another thread so a plugin that streams its output can't
+    // fill the stdout pipe while the write is still blocked. Dropping stdin
+    // when the write is done closes it, so the plugin sees EOF.
+    let writer = child
+        .stdin
+        .take()
+        .map(|mut stdin| std::thread::spawn(move || stdin.write_all(json.as_bytes())));
+
+    let result = child
+        .wait_with_output()
+        .map_err(|e| format!("{} | Error {}", plugin, e))?;
+
+    // A plugin may stop reading once it has what it needs.
+    if let Some(Ok(Err(e))) = writer.map(|writer| writer.join()) {
+        if e.kind() != ErrorKind::BrokenPipe {
+            return Err(format!("{} | Error {}", plugin, e));
+        }
+    }
+
+    if result.status.success() {
+        String::from_utf8(result.stdout).map_err(|e| e.to_string())
+    } else {
+        Err(format!("{} exited with {}", plugin, result.status))
+    }
+}
+
+// Alerts
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+struct AlertReport {
+    within_days: u16,
+    events: Vec<FutureEvent>,
+}
+
+// Parses a number of days, optionally suffixed with `d` ex: 14d
+fn parse_days(days: &str) -> Result<u16, String> {
+    days.strip_suffix('d')
+        .unwrap_or(days)
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid number of days: {}", days))
+}
+
+// Upcoming events at most `days` away, soonest first. Deferred events are
+// included since alerts are about the real date, tentative ones aren't until
+// their date is confirmed.
+fn events_due_within(now: SystemTime, events: &[Event], days: u16) -> Vec<FutureEvent> {
+    let due: Vec<FutureEvent> = filter_expired_events(now, events)
+        .into_iter()
+        .filter(|ev| ev.days_left <= days && !ev.tentative)
+        .collect();
+
+    events_sorted_by_time(&due, true)
+}
+
+// Watch export
+
+// Entry in the compact format read by common watch-face tools.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+struct WatchEntry {
+    label: String,
+    target_iso: String,
+}
+
+// Nearest shown events first, the same ones the default listing would show.
+fn watch_entries(now: SystemTime, events: Vec<Event>, limit: Option<usize>) -> Vec<WatchEntry> {
+    let mut upcoming: Vec<Event> = filter_deferred_events(now, events)
+        .into_iter()
+        .filter(|ev| ev.days_left(now).is_some())
+        .collect();
+    upcoming.sort_by_key(|ev| ev.time);
+
+    upcoming
+        .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|ev| WatchEntry {
+            target_iso: format_timestamp(ev.time.into(), "%Y-%m-%dT%H:%M:%SZ"),
+            label: tentative_label(&ev.name, ev.tentative),
+        })
+        .collect()
+}
+
+// Usage report
+
+// Anonymized snapshot of a store. Only coarse buckets are kept so that the
+// report can't be used to recover event names, dates or exact counts.
+#[derive(Debug, Clone, PartialEq)]
+struct UsageReport {
+    version: &'static str,
+    events: &'static str,
+    upcoming: &'static str,
+    expired: &'static str,
+    store_size: &'static str,
+}
+
+impl std::fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "version = {}", self.version)?;
+        writeln!(f, "events = {}", self.events)?;
+        writeln!(f, "upcoming = {}", self.upcoming)?;
+        writeln!(f, "expired = {}", self.expired)?;
+        writeln!(f, "store_size = {}", self.store_size)
+    }
+}
+
+fn count_bucket(count: usize) -> &'static str {
+    match count {
+        0 => "0",
+        1..=9 => "1-9",
+        10..=49 => "10-49",
+        50..=199 => "50-199",
+        _ => "200+",
+    }
+}
+
+fn bytes_bucket(bytes: u64) -> &'static str {
+    match bytes {
+        0 => "empty",
+        1..=1023 => "<1KiB",
+        1024..=10239 => "1-10KiB",
+        10240..=102399 => "10-100KiB",
+        _ => "100KiB+",
+    }
+}
+
+fn usage_report(now: SystemTime, events: &[Event], store_bytes: u64) -> UsageReport {
+    let upcoming = filter_expired_events(now, events).len();
+
+    UsageReport {
+        version: env!("CARGO_PKG_VERSION"),
+        events: count_bucket(events.len()),
+        upcoming: count_bucket(upcoming),
+        expired: count_bucket(events.len() - upcoming),
+        store_size: bytes_bucket(store_bytes),
+    }
+}
+
+fn applicable_events(
+    now: SystemTime,
+    events: Vec<Event>,
+    args: &CountdownArgs,
+) -> Vec<FutureEvent> {
+    let shown = filter_deferred_events(now, events);
+    let current = filter_expired_events(now, &shown);
+    let sorted = sort_events(&current, &args.order);
+
+    limit_events(sorted, args.n)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // Event
+    #[test]
+    fn event_days_left_calculates_remaining_days_correctly() {
+        let event = Event {
+            name: "test".to_string(),
+            time: 172800,
+            defer_until: None,
+            tentative: false,
+        };
+        let result = event.days_left(UNIX_EPOCH);
+
+        assert_eq!(result, Some(2));
+    }
+
+    #[test]
+    fn event_days_left_returns_none_if_expired() {
+        let event = Event {
+            name: "test".to_string(),
+            time: 5000,
+            defer_until: None,
+            tentative: false,
+        };
+        let result = event.days_left(UNIX_EPOCH + Duration::from_secs(10000));
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn event_as_future_event_returns_future_event_if_not_expired() {
+        let event = Event {
+            name: "test".to_string(),
+            time: 172800,
+            defer_until: None,
+            tentative: false,
+        };
+        let result = event.as_future_event(UNIX_EPOCH);
+
+        assert_eq!(
+            result,
+            Some(FutureEvent {
+                name: "test".to_string(),
+                days_left: 2,
+                tentative: false,
+            })
+        );
+    }
+
+    #[test]
+    fn event_as_future_event_returns_none_if_expired() {
+        let event = Event {
+            name: "test".to_string(),
+            time: 172800,
+            defer_until: None,
+            tentative: false,
+        };
+        let result = event.as_future_event(UNIX_EPOCH + Duration::from_secs(172801));
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn filter_expired_events_removes_expired_events() {
+        let events = vec![
+            Event {
+                name: "expired 1".to_string(),
+                time: 900,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "not expired 1".to_string(),
+                time: 1020,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "expired 3".to_string(),
+                time: 543,
+                defer_until: None,
+                tentative: false,
+            },
+        ];
+        let result = filter_expired_events(UNIX_EPOCH + Duration::from_secs(1000), &events);
+
+        assert_eq!(
+            result,
+            vec![FutureEvent {
+                name: "not expired 1".to_string(),
+                days_left: 0,
+                tentative: false
+            }],
+        );
+    }
+
+    #[test]
+    fn defer_event_sets_defer_until_on_matching_events() {
+        let mut events = vec![
+            Event {
+                name: "trip".to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "other".to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: false,
+            },
+        ];
+        let result = defer_event(&mut events, "trip", 86400);
+
+        assert_eq!(result, 1);
+        assert_eq!(events[0].defer_until, Some(86400));
+        assert_eq!(events[0].time, 172800);
+        assert_eq!(events[1].defer_until, None);
     }
 
     #[test]
-    fn filter_expired_events_removes_expired_events() {
+    fn filter_deferred_events_hides_events_until_their_date() {
         let events = vec![
             Event {
-                name: "expired 1".to_string(),
-                time: 900,
+                name: "deferred".to_string(),
+                time: 172800,
+                defer_until: Some(86400),
+                tentative: false,
             },
             Event {
-                name: "not expired 1".to_string(),
-                time: 1020,
+                name: "shown again".to_string(),
+                time: 172800,
+                defer_until: Some(500),
+                tentative: false,
+            },
+        ];
+        let result = filter_deferred_events(UNIX_EPOCH + Duration::from_secs(1000), events);
+
+        assert_eq!(
+            result,
+            vec![Event {
+                name: "shown again".to_string(),
+                time: 172800,
+                defer_until: Some(500),
+                tentative: false,
+            }],
+        );
+    }
+
+    #[test]
+    fn compact_events_sorts_dedups_and_drops_passed_deferrals() {
+        let events = vec![
+            Event {
+                name: "later".to_string(),
+                time: 172800,
+                defer_until: Some(86400),
+                tentative: false,
             },
             Event {
-                name: "expired 3".to_string(),
-                time: 543,
+                name: "sooner".to_string(),
+                time: 86400,
+                defer_until: Some(500),
+                tentative: false,
+            },
+            Event {
+                name: "sooner".to_string(),
+                time: 86400,
+                defer_until: None,
+                tentative: false,
             },
         ];
-        let result = filter_expired_events(UNIX_EPOCH + Duration::from_secs(1000), &events);
+        let result = compact_events(UNIX_EPOCH + Duration::from_secs(1000), events);
+
+        assert_eq!(
+            result,
+            vec![
+                Event {
+                    name: "sooner".to_string(),
+                    time: 86400,
+                    defer_until: None,
+                    tentative: false,
+                },
+                Event {
+                    name: "later".to_string(),
+                    time: 172800,
+                    defer_until: Some(86400),
+                    tentative: false,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn compact_events_dedups_interleaved_tentative_copies() {
+        let events = vec![
+            Event {
+                name: "trip".to_string(),
+                time: 86400,
+                defer_until: None,
+                tentative: true,
+            },
+            Event {
+                name: "trip".to_string(),
+                time: 86400,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "trip".to_string(),
+                time: 86400,
+                defer_until: None,
+                tentative: true,
+            },
+        ];
+        let result: Vec<bool> = compact_events(UNIX_EPOCH, events)
+            .iter()
+            .map(|ev| ev.tentative)
+            .collect();
+
+        assert_eq!(result, vec![false, true]);
+    }
+
+    #[test]
+    fn confirm_event_clears_tentative_on_matching_events() {
+        let mut events = vec![
+            Event {
+                name: "trip".to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: true,
+            },
+            Event {
+                name: "other".to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: true,
+            },
+        ];
+        let result = confirm_event(&mut events, "trip");
+
+        assert_eq!(result, 1);
+        assert!(!events[0].tentative);
+        assert!(events[1].tentative);
+    }
+
+    #[test]
+    fn check_clock_fails_when_time_is_before_the_newest_snapshot() {
+        let index = "86400 aaaa\n172800 bbbb\n";
+
+        assert!(check_clock(UNIX_EPOCH + Duration::from_secs(172800), index).is_ok());
+        assert!(check_clock(UNIX_EPOCH + Duration::from_secs(100000), index).is_err());
+        assert!(check_clock(UNIX_EPOCH, "").is_ok());
+    }
+
+    #[test]
+    fn parse_date_rejects_dates_outside_the_store_range() {
+        assert_eq!(parse_date("1-1-1970"), Ok(0));
+        assert_eq!(parse_date("7-2-2106"), Ok(4294944000));
+        assert!(parse_date("31-12-1969").is_err());
+        assert!(parse_date("8-2-2106").is_err());
+        assert!(parse_date("5-6-30").is_err());
+    }
+
+    #[test]
+    fn parse_days_accepts_optional_suffix() {
+        assert_eq!(parse_days("14d"), Ok(14));
+        assert_eq!(parse_days("14"), Ok(14));
+        assert!(parse_days("two weeks").is_err());
+    }
+
+    #[test]
+    fn events_due_within_keeps_only_confirmed_events_inside_window() {
+        let events = vec![
+            Event {
+                name: "later".to_string(),
+                time: 10 * 86400,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "soon".to_string(),
+                time: 2 * 86400,
+                defer_until: Some(5 * 86400),
+                tentative: false,
+            },
+            Event {
+                name: "expired".to_string(),
+                time: 500,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "unconfirmed".to_string(),
+                time: 3 * 86400,
+                defer_until: None,
+                tentative: true,
+            },
+        ];
+        let result = events_due_within(UNIX_EPOCH + Duration::from_secs(1000), &events, 7);
 
         assert_eq!(
             result,
             vec![FutureEvent {
-                name: "not expired 1".to_string(),
-                days_left: 0
+                name: "soon".to_string(),
+                days_left: 1,
+                tentative: false,
+            }],
+        );
+    }
+
+    #[test]
+    fn watch_entries_lists_nearest_events_first() {
+        let events = vec![
+            Event {
+                name: "later".to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "sooner".to_string(),
+                time: 86400,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "expired".to_string(),
+                time: 500,
+                defer_until: None,
+                tentative: false,
+            },
+        ];
+        let result = watch_entries(UNIX_EPOCH + Duration::from_secs(1000), events, Some(1));
+
+        assert_eq!(
+            result,
+            vec![WatchEntry {
+                label: "sooner".to_string(),
+                target_iso: "1970-01-02T00:00:00Z".to_string(),
             }],
         );
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            r#"[{"label":"sooner","target_iso":"1970-01-02T00:00:00Z"}]"#,
+        );
+    }
+
+    #[test]
+    fn watch_entries_mark_tentative_events() {
+        let events = vec![Event {
+            name: "trip".to_string(),
+            time: 86400,
+            defer_until: None,
+            tentative: true,
+        }];
+        let result = watch_entries(UNIX_EPOCH, events, None);
+
+        assert_eq!(result[0].label, "trip (tentative)");
+    }
+
+    #[test]
+    fn format_events_uses_builtin_formatters() {
+        let events = vec![FutureEvent {
+            name: "test".to_string(),
+            days_left: 2,
+            tentative: false,
+        }];
+
+        assert_eq!(
+            format_events(OUTPUT_TEXT, &events),
+            Ok("2 days until test\n".to_string())
+        );
+        assert_eq!(
+            format_events(OUTPUT_JSON, &events),
+            Ok("[\n  {\n    \"name\": \"test\",\n    \"days_left\": 2\n  }\n]\n".to_string())
+        );
+    }
+
+    #[test]
+    fn format_events_marks_tentative_events() {
+        let events = vec![FutureEvent {
+            name: "test".to_string(),
+            days_left: 2,
+            tentative: true,
+        }];
+
+        assert_eq!(
+            format_events(OUTPUT_TEXT, &events),
+            Ok("2 days until test (tentative)\n".to_string())
+        );
+        assert_eq!(
+            format_events(OUTPUT_JSON, &events),
+            Ok(
+                "[\n  {\n    \"name\": \"test\",\n    \"days_left\": 2,\n    \"tentative\": true\n  }\n]\n"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn format_events_reports_unknown_outputs() {
+        let result = format_events("no-such-output", &[]);
+
+        assert!(result
+            .unwrap_err()
+            .starts_with("Unknown output 'no-such-output'"));
+    }
+
+    #[test]
+    fn format_events_only_runs_plugins_by_plain_name() {
+        for output in ["/bin/true", "../x", "a/b", "x y", ""] {
+            assert!(format_events(output, &[])
+                .unwrap_err()
+                .starts_with("Unknown output"));
+        }
+    }
+
+    #[test]
+    fn closest_match_suggests_similar_names() {
+        let names = ["birthday", "holiday", "launch"];
+
+        assert_eq!(closest_match("birthdy", names), Some("birthday"));
+        assert_eq!(closest_match("holidya", names), Some("holiday"));
+        assert_eq!(closest_match("xyz", names), None);
+    }
+
+    #[test]
+    fn format_events_suggests_close_builtin_outputs() {
+        let result = format_events("jsn", &[]);
+
+        assert!(result
+            .unwrap_err()
+            .starts_with("Unknown output 'jsn', did you mean 'json'?"));
+    }
+
+    #[test]
+    fn listing_options_are_refused_with_a_subcommand() {
+        let words = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+
+        assert!(parse_command_words(&words("--output json")).is_ok());
+        assert!(parse_command_words(&words("--output json alert -w 3")).is_err());
+        assert!(parse_command_words(&words("-n 2 export-watch")).is_err());
+        assert!(parse_command_words(&words("at 1-1-2030 --output json")).is_ok());
+        assert!(parse_command_words(&words("alert -w 3 --events-file ev.toml")).is_err());
+    }
+
+    #[test]
+    fn run_command_edits_the_in_memory_store() {
+        let mut config = CountdownConfig::default();
+        let args = parse_command_line("add-event -e 'big trip' -d 1-1-2030").unwrap();
+        let result = run_command(UNIX_EPOCH, &args, Path::new("unused"), &mut config);
+
+        assert_eq!(result, Ok(Outcome::Changed));
+        assert_eq!(config.events[0].name, "big trip");
+    }
+
+    #[test]
+    fn parse_batch_reports_the_first_invalid_line() {
+        let input = "# provisioning\nadd-event -e one -d 1-1-2030\n\nadd-evnt -e two\n";
+
+        assert!(parse_batch(input).unwrap_err().starts_with("Line 4: "));
+    }
+
+    #[test]
+    fn apply_batch_stops_at_the_first_failing_command() {
+        let mut config = CountdownConfig::default();
+        let commands =
+            parse_batch("add-event -e one -d 1-1-2030\ndefer two -u 1-1-2029\n").unwrap();
+        let result = apply_batch(UNIX_EPOCH, &commands, Path::new("unused"), &mut config);
+
+        assert_eq!(result, Err("Line 2: No event named 'two'".to_string()));
+    }
+
+    #[test]
+    fn freeform_candidates_finds_dated_lines() {
+        let text =
+            "- 14-07-2030 Bastille day trip\nno date here\nDentist: 2030-01-02.\n01-01-2030\n\
+                    meeting 5-6-30\nmoon landing 31-12-1969\nfar off 1/1/2107\n";
+        let result: Vec<String> = freeform_candidates(text)
+            .into_iter()
+            .map(|ev| ev.name)
+            .collect();
+
+        assert_eq!(result, vec!["Bastille day trip", "Dentist"]);
+    }
+
+    #[test]
+    fn choose_candidates_follows_answers() {
+        let candidates: Vec<Event> = ["one", "two", "three", "four"]
+            .iter()
+            .map(|name| Event {
+                name: name.to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: false,
+            })
+            .collect();
+        let result = choose_candidates(candidates, &mut "y\nn\na\n".as_bytes()).unwrap();
+        let names: Vec<&str> = result.iter().map(|ev| ev.name.as_str()).collect();
+
+        assert_eq!(names, vec!["one", "three", "four"]);
+    }
+
+    #[test]
+    fn parse_federation_resolves_paths_from_its_directory() {
+        let federation = parse_federation(
+            "[[sources]]\nname = 'team'\npath = 'team.toml'\n\n\
+             [[sources]]\nname = 'shared'\npath = '/shared/team.toml'\n",
+            Path::new("/home/me/.config/countdown"),
+        )
+        .unwrap();
+        let paths: Vec<&Path> = federation
+            .sources
+            .iter()
+            .map(|source| source.path.as_path())
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("/home/me/.config/countdown/team.toml"),
+                Path::new("/shared/team.toml")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_federation_rejects_reserved_and_duplicate_names() {
+        let dir = Path::new("/home/me/.config/countdown");
+
+        assert!(
+            parse_federation("[[sources]]\nname = 'personal'\npath = 'a.toml'\n", dir)
+                .unwrap_err()
+                .contains("reserved")
+        );
+        assert!(parse_federation(
+            "[[sources]]\nname = 'team'\npath = 'a.toml'\n\n\
+             [[sources]]\nname = 'team'\npath = 'b.toml'\n",
+            dir
+        )
+        .unwrap_err()
+        .contains("more than once"));
+    }
+
+    #[test]
+    fn enabled_sources_applies_runtime_overrides() {
+        let sources: Vec<Source> = toml::from_str::<FederationConfig>(
+            r#"
+        [[sources]]
+        name = 'team'
+        path = '/shared/team.toml'
+
+        [[sources]]
+        name = 'old'
+        path = '/shared/old.toml'
+        enabled = false
+    "#,
+        )
+        .unwrap()
+        .sources;
+        let names = |enable: &[&str], disable: &[&str]| {
+            let enable: Vec<String> = enable.iter().map(|s| s.to_string()).collect();
+            let disable: Vec<String> = disable.iter().map(|s| s.to_string()).collect();
+            enabled_sources(&sources, &enable, &disable).map(|all| {
+                all.into_iter()
+                    .map(|source| source.name)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            names(&[], &[]),
+            Ok(vec!["personal".to_string(), "team".to_string()])
+        );
+        assert_eq!(
+            names(&["old"], &["personal"]),
+            Ok(vec!["team".to_string(), "old".to_string()])
+        );
+        assert_eq!(
+            names(&[], &["taem"]),
+            Err("Unknown source 'taem', did you mean 'team'?".to_string())
+        );
+    }
+
+    #[test]
+    fn federated_events_merges_sources_soonest_first() {
+        let event = |name: &str, time: u32| Event {
+            name: name.to_string(),
+            time,
+            defer_until: None,
+            tentative: false,
+        };
+        let result = federated_events(
+            UNIX_EPOCH + Duration::from_secs(1000),
+            vec![
+                ("personal".to_string(), vec![event("later", 172800)]),
+                (
+                    "team".to_string(),
+                    vec![event("sooner", 86400), event("expired", 500)],
+                ),
+            ],
+        );
+        let labels: Vec<(&str, &str)> = result
+            .iter()
+            .map(|(source, ev)| (source.as_str(), ev.name.as_str()))
+            .collect();
+
+        assert_eq!(labels, vec![("team", "sooner"), ("personal", "later")]);
+    }
+
+    #[test]
+    fn content_hash_is_stable() {
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn snapshot_at_picks_newest_snapshot_before_time() {
+        let index = "100 aaaa\n200 bbbb\n300 cccc\n";
+
+        assert_eq!(snapshot_at(index, 50), None);
+        assert_eq!(snapshot_at(index, 200), Some("bbbb"));
+        assert_eq!(snapshot_at(index, 250), Some("bbbb"));
+        assert_eq!(snapshot_at(index, u64::MAX), Some("cccc"));
+    }
+
+    #[test]
+    fn retained_entries_keeps_the_last_of_each_day_within_retention() {
+        let day = SECONDS_IN_DAY;
+        let now = 400 * day + 3600;
+        let index = format!(
+            "{} old1\n{} old2\n{} kept1\n{} kept2\n{} today1\n{} today2\n",
+            10 * day,
+            20 * day,
+            40 * day,
+            40 * day + 60,
+            now - 60,
+            now
+        );
+
+        assert_eq!(
+            retained_entries(&index, now),
+            vec![
+                (20 * day, "old2"),
+                (40 * day + 60, "kept2"),
+                (now, "today2")
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_at_handles_the_clock_jumping_back() {
+        let index = "100 aaaa\n300 bbbb\n200 cccc\n400 dddd\n";
+
+        assert_eq!(snapshot_at(index, 250), Some("cccc"));
+        assert_eq!(snapshot_at(index, 350), Some("bbbb"));
+        assert_eq!(snapshot_at(index, 400), Some("dddd"));
+    }
+
+    #[cfg(feature = "shell")]
+    #[test]
+    fn word_start_skips_multibyte_whitespace() {
+        assert_eq!(word_start("defer tr"), 6);
+        assert_eq!(word_start("defer\u{a0}tr"), 7);
+        assert_eq!(word_start("defer"), 0);
+        assert_eq!(word_start("defer "), 6);
+    }
+
+    #[cfg(feature = "shell")]
+    #[test]
+    fn shell_helper_completes_subcommands_flags_and_names() {
+        let config = CountdownConfig {
+            events: vec![Event {
+                name: "big trip".to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: false,
+            }],
+        };
+        let helper = ShellHelper::new(&config);
+
+        assert_eq!(helper.candidates("de", "de"), vec!["defer"]);
+        assert_eq!(helper.candidates("defer --u", "--u"), vec!["--until"]);
+        assert_eq!(helper.candidates("defer b", "b"), vec!["'big trip'"]);
     }
 
     #[test]
@@ -375,14 +2457,17 @@ mod tests {
             FutureEvent {
                 name: "test 1".to_string(),
                 days_left: 900,
+                tentative: false,
             },
             FutureEvent {
                 name: "test 2".to_string(),
                 days_left: 1020,
+                tentative: false,
             },
             FutureEvent {
                 name: "test 3".to_string(),
                 days_left: 543,
+                tentative: false,
             },
         ];
         let result = sort_events(&events, &Some(SortOrder::TimeAsc));
@@ -392,15 +2477,18 @@ mod tests {
             vec![
                 FutureEvent {
                     name: "test 3".to_string(),
-                    days_left: 543
+                    days_left: 543,
+                    tentative: false
                 },
                 FutureEvent {
                     name: "test 1".to_string(),
-                    days_left: 900
+                    days_left: 900,
+                    tentative: false
                 },
                 FutureEvent {
                     name: "test 2".to_string(),
-                    days_left: 1020
+                    days_left: 1020,
+                    tentative: false
                 },
             ],
         );
@@ -412,14 +2500,17 @@ mod tests {
             FutureEvent {
                 name: "test 1".to_string(),
                 days_left: 900,
+                tentative: false,
             },
             FutureEvent {
                 name: "test 2".to_string(),
                 days_left: 1020,
+                tentative: false,
             },
             FutureEvent {
                 name: "test 3".to_string(),
                 days_left: 543,
+                tentative: false,
             },
         ];
         let result = sort_events(&events, &Some(SortOrder::TimeDesc));
@@ -429,29 +2520,66 @@ mod tests {
             vec![
                 FutureEvent {
                     name: "test 2".to_string(),
-                    days_left: 1020
+                    days_left: 1020,
+                    tentative: false
                 },
                 FutureEvent {
                     name: "test 1".to_string(),
-                    days_left: 900
+                    days_left: 900,
+                    tentative: false
                 },
                 FutureEvent {
                     name: "test 3".to_string(),
-                    days_left: 543
+                    days_left: 543,
+                    tentative: false
                 },
             ],
         );
     }
 
+    #[test]
+    fn usage_report_only_contains_buckets() {
+        let events = vec![
+            Event {
+                name: "expired".to_string(),
+                time: 500,
+                defer_until: None,
+                tentative: false,
+            },
+            Event {
+                name: "upcoming".to_string(),
+                time: 172800,
+                defer_until: None,
+                tentative: false,
+            },
+        ];
+        let result = usage_report(UNIX_EPOCH + Duration::from_secs(1000), &events, 2048);
+
+        assert_eq!(
+            result,
+            UsageReport {
+                version: env!("CARGO_PKG_VERSION"),
+                events: "1-9",
+                upcoming: "1-9",
+                expired: "1-9",
+                store_size: "1-10KiB",
+            }
+        );
+    }
+
     #[test]
     fn test_inside_toml() {
         let event = Event {
             name: "String".to_string(),
             time: 12312312,
+            defer_until: None,
+            tentative: false,
         };
         let event1 = Event {
             name: "String".to_string(),
             time: 12312312,
+            defer_until: None,
+            tentative: false,
         };
         let c = CountdownConfig {
             events: vec![event, event1],
@@ -470,14 +2598,54 @@ mod tests {
         )
         .unwrap();
 
-        assert_eq!(
-            toml::to_string(&config.events[0]).unwrap(),
+        assert_eq!(config.events, c.events);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected_by_the_strict_schema() {
+        let result = toml::from_str::<StrictConfig>(
             r#"
-            [[events]]
-            name = 'String'
-            time = 12312312
-        "#,
+        [[events]]
+        name = 'String'
+        tiem = 12312312
+    "#,
         );
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("unknown field `tiem`"));
+    }
+
+    #[test]
+    fn strict_schema_accepts_every_field_an_event_can_have() {
+        let config = CountdownConfig {
+            events: vec![Event {
+                name: "trip".to_string(),
+                time: 172800,
+                defer_until: Some(86400),
+                tentative: true,
+            }],
+        };
+        let toml = toml::to_string(&config).unwrap();
+
+        assert!(toml::from_str::<StrictConfig>(&toml).is_ok());
+        assert!(toml::from_str::<CountdownConfig>(&(toml + "colour = 'red'\n")).is_ok());
+    }
+
+    #[test]
+    fn events_file_config_refuses_writing_commands() {
+        let events_file = Path::new("Cargo.toml");
+        let add = Some(ESubCommands::AddEvent {
+            event: "test".to_string(),
+            date: "1-1-2030".to_string(),
+            tentative: false,
+        });
+
+        assert!(events_file_config(events_file, &add).is_err());
+        assert!(events_file_config(events_file, &Some(ESubCommands::UsageReport)).is_ok());
+        assert!(events_file_config(events_file, &None).is_ok());
+        assert!(events_file_config(Path::new("missing.toml"), &None).is_err());
     }
 
     #[test]
@@ -488,6 +2656,6 @@ mod tests {
 
         let output = NaiveDate::parse_from_str(output_q, "%d-%m-%Y").unwrap();
 
-        assert_ne!(output, NaiveDate::from_ymd(2102, 2, 3));
+        assert_ne!(output, NaiveDate::from_ymd_opt(2102, 2, 3).unwrap());
     }
 }
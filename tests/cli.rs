@@ -0,0 +1,644 @@
+// End-to-end tests running the countdown binary against a temporary home
+// directory. Outputs that are part of the CLI surface are compared against
+// files in tests/golden. Run with UPDATE_GOLDEN=1 to rewrite them.
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+
+const FIXTURE_STORE: &str = r#"[[events]]
+name = 'Launch'
+time = 3792009600
+
+[[events]]
+name = 'Conference'
+time = 3788121600
+"#;
+
+// Start of the day the fixture's countdowns are measured from, for `at`.
+const FIXTURE_DATE: &str = "1-1-2090";
+
+struct Home {
+    dir: TempDir,
+}
+
+impl Home {
+    fn new() -> Self {
+        Self {
+            dir: TempDir::new().unwrap(),
+        }
+    }
+
+    // A home whose store holds the fixture events, with a snapshot of them
+    // old enough for `at` to find.
+    fn with_fixture() -> Self {
+        let home = Self::new();
+        fs::create_dir_all(home.snapshots()).unwrap();
+        fs::write(home.store(), FIXTURE_STORE).unwrap();
+        fs::write(home.snapshots().join("fixture.toml"), FIXTURE_STORE).unwrap();
+        fs::write(home.snapshots().join("index"), "0 fixture\n").unwrap();
+
+        home
+    }
+
+    fn config_dir(&self) -> PathBuf {
+        self.dir.path().join(".config/countdown")
+    }
+
+    fn store(&self) -> PathBuf {
+        self.config_dir().join("countdown.toml")
+    }
+
+    fn snapshots(&self) -> PathBuf {
+        self.config_dir().join("snapshots")
+    }
+
+    fn countdown(&self) -> Command {
+        let mut cmd = Command::cargo_bin("countdown").unwrap();
+        cmd.env("HOME", self.dir.path());
+
+        cmd
+    }
+
+    fn read_store(&self) -> String {
+        fs::read_to_string(self.store()).unwrap()
+    }
+}
+
+fn golden(name: &str, actual: &[u8]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    let actual = String::from_utf8_lossy(actual);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual.as_bytes()).unwrap();
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing golden file {}", path.display()));
+    assert_eq!(actual, expected, "output differs from {}", path.display());
+}
+
+fn stdout_of(cmd: &mut Command) -> Vec<u8> {
+    cmd.assert().success().get_output().stdout.clone()
+}
+
+#[test]
+fn help_lists_every_subcommand() {
+    let home = Home::new();
+
+    golden("help.txt", &stdout_of(home.countdown().arg("--help")));
+}
+
+#[test]
+fn listing_without_events_asks_for_some() {
+    let home = Home::new();
+
+    home.countdown()
+        .assert()
+        .code(2)
+        .stderr("No Entires!. Please add events first\n");
+}
+
+#[test]
+fn listing_text_output() {
+    let home = Home::with_fixture();
+
+    golden(
+        "list.txt",
+        &stdout_of(home.countdown().args(["at", FIXTURE_DATE])),
+    );
+}
+
+#[test]
+fn listing_respects_order_and_limit() {
+    let home = Home::with_fixture();
+
+    golden(
+        "list-time-desc-n1.txt",
+        &stdout_of(
+            home.countdown()
+                .args(["at", FIXTURE_DATE, "-o", "time-desc", "-n", "1"]),
+        ),
+    );
+}
+
+#[test]
+fn listing_json_output() {
+    let home = Home::with_fixture();
+
+    golden(
+        "list.json",
+        &stdout_of(
+            home.countdown()
+                .args(["at", FIXTURE_DATE, "--output", "json"]),
+        ),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn listing_plugin_output() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = Home::with_fixture();
+    let bin = home.dir.path().join("bin");
+    let plugin = bin.join("countdown-format-upper");
+    fs::create_dir_all(&bin).unwrap();
+    fs::write(&plugin, "#!/bin/sh\ntr a-z A-Z\n").unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    golden(
+        "list-plugin.txt",
+        &stdout_of(home.countdown().env("PATH", path).args([
+            "at",
+            FIXTURE_DATE,
+            "--output",
+            "upper",
+        ])),
+    );
+}
+
+// The payload is far bigger than a pipe buffer, so a plugin that echoes its
+// input as it reads only finishes if stdin and stdout are serviced together.
+#[cfg(unix)]
+#[test]
+fn listing_plugin_output_with_a_large_store() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = Home::new();
+    fs::create_dir_all(home.config_dir()).unwrap();
+    let store: String = (0..6000)
+        .map(|i| format!("[[events]]\nname = 'Event {}'\ntime = 3792009600\n\n", i))
+        .collect();
+    fs::write(home.store(), store).unwrap();
+
+    let bin = home.dir.path().join("bin");
+    let plugin = bin.join("countdown-format-cat");
+    fs::create_dir_all(&bin).unwrap();
+    fs::write(&plugin, "#!/bin/sh\ncat\n").unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let output = stdout_of(
+        home.countdown()
+            .env("PATH", path)
+            .args(["--output", "cat"])
+            .timeout(Duration::from_secs(30)),
+    );
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.matches("\"name\"").count(), 6000);
+}
+
+#[test]
+fn listing_unknown_output_suggests_builtin() {
+    let home = Home::with_fixture();
+
+    home.countdown()
+        .args(["--output", "jsno"])
+        .assert()
+        .code(2)
+        .stderr(predicates::str::starts_with(
+            "Unknown output 'jsno', did you mean 'json'?",
+        ));
+}
+
+#[test]
+fn listing_options_are_refused_with_a_subcommand() {
+    let home = Home::with_fixture();
+
+    home.countdown()
+        .args(["--output", "json", "alert", "-w", "3"])
+        .assert()
+        .code(2)
+        .stderr("-o, -n and --output only apply to the listing, not to subcommands\n");
+}
+
+#[test]
+fn add_event_creates_the_store() {
+    let home = Home::new();
+
+    home.countdown()
+        .args(["add-event", "-e", "Launch", "-d", "1-3-2090"])
+        .assert()
+        .success()
+        .stdout("Added!\n");
+    home.countdown()
+        .args(["add-event", "-e", "Conference", "-d", "15-1-2090"])
+        .assert()
+        .success();
+
+    golden("store-after-add.toml", home.read_store().as_bytes());
+}
+
+#[test]
+fn add_event_rejects_bad_dates() {
+    let home = Home::new();
+
+    home.countdown()
+        .args(["add-event", "-e", "Launch", "-d", "2090-03-01"])
+        .assert()
+        .code(2)
+        .stderr("Date string in wrong format\n");
+    assert!(!home.store().exists());
+}
+
+#[test]
+fn defer_hides_an_event_from_the_listing() {
+    let home = Home::with_fixture();
+
+    home.countdown()
+        .args(["defer", "Conference", "--until", "1-1-2095"])
+        .assert()
+        .success()
+        .stdout("Deferred!\n");
+
+    let listing = String::from_utf8(stdout_of(&mut home.countdown())).unwrap();
+    assert!(listing.contains("Launch"));
+    assert!(!listing.contains("Conference"));
+    assert!(home.read_store().contains("defer_until = 3944678400"));
+}
+
+#[test]
+fn defer_unknown_event_suggests_a_name() {
+    let home = Home::with_fixture();
+
+    home.countdown()
+        .args(["defer", "Conferense", "-u", "1-1-2095"])
+        .assert()
+        .code(2)
+        .stderr("No event named 'Conferense', did you mean 'Conference'?\n");
+}
+
+#[test]
+fn tentative_events_are_marked_and_skip_alerts_until_confirmed() {
+    let home = Home::new();
+
+    home.countdown()
+        .args(["add-event", "-e", "Launch", "-d", "1-3-2090", "--tentative"])
+        .assert()
+        .success();
+    let listing = String::from_utf8(stdout_of(&mut home.countdown())).unwrap();
+    assert!(listing.ends_with("days until Launch (tentative)\n"));
+    home.countdown()
+        .args(["alert", "--within", "50000"])
+        .assert()
+        .code(0)
+        .stdout("");
+
+    home.countdown()
+        .args(["confirm", "Lanuch"])
+        .assert()
+        .code(2)
+        .stderr("No event named 'Lanuch', did you mean 'Launch'?\n");
+    home.countdown()
+        .args(["confirm", "Launch"])
+        .assert()
+        .success()
+        .stdout("Confirmed!\n");
+    home.countdown()
+        .args(["alert", "--within", "50000"])
+        .assert()
+        .code(1)
+        .stdout(predicates::str::ends_with("days until Launch\n"));
+}
+
+#[test]
+fn alert_text_output_and_exit_codes() {
+    let home = Home::with_fixture();
+
+    let due = home
+        .countdown()
+        .args(["at", FIXTURE_DATE, "alert", "--within", "14d"])
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+    golden("alert.txt", &due);
+
+    home.countdown()
+        .args(["at", FIXTURE_DATE, "alert", "--within", "13d"])
+        .assert()
+        .code(0)
+        .stdout("");
+    Home::new()
+        .countdown()
+        .args(["alert", "--within", "14d"])
+        .assert()
+        .code(0)
+        .stdout("");
+    home.countdown()
+        .args(["alert", "--within", "soon"])
+        .assert()
+        .code(2)
+        .stderr("Invalid number of days: soon\n");
+}
+
+#[test]
+fn alert_json_output() {
+    let home = Home::with_fixture();
+
+    let due = home
+        .countdown()
+        .args(["at", FIXTURE_DATE, "alert", "-w", "90", "--json"])
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+    golden("alert.json", &due);
+}
+
+#[test]
+fn export_watch_prints_and_writes() {
+    let home = Home::new();
+    home.countdown()
+        .args(["add-event", "-e", "Far off", "-d", "1-6-2096"])
+        .assert()
+        .success();
+
+    let printed = stdout_of(home.countdown().arg("export-watch"));
+    golden("export-watch.json", &printed);
+
+    home.countdown()
+        .args(["export-watch", "--write"])
+        .assert()
+        .success();
+    let written = fs::read_to_string(home.config_dir().join("watch.json")).unwrap();
+    assert_eq!(written + "\n", String::from_utf8(printed).unwrap());
+}
+
+#[test]
+fn export_watch_of_an_empty_store_is_an_empty_array() {
+    let home = Home::new();
+
+    home.countdown()
+        .arg("export-watch")
+        .assert()
+        .success()
+        .stdout("[]\n");
+    home.countdown()
+        .args(["export-watch", "--write"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(home.config_dir().join("watch.json")).unwrap(),
+        "[]"
+    );
+}
+
+#[test]
+fn usage_report_output() {
+    let home = Home::with_fixture();
+
+    golden(
+        "usage-report.txt",
+        &stdout_of(home.countdown().args(["at", FIXTURE_DATE, "usage-report"])),
+    );
+}
+
+#[test]
+fn compact_rewrites_the_store_in_canonical_order() {
+    let home = Home::new();
+    fs::create_dir_all(home.config_dir()).unwrap();
+    fs::write(
+        home.store(),
+        "# noisy\n[[events]]\nname = 'Launch'\ntime = 3792009600\n\n\
+         [[events]]\nname = 'Conference'\ntime = 3788121600\n\n\
+         [[events]]\nname = 'Launch'\ntime = 3792009600\ndefer_until = 0\n",
+    )
+    .unwrap();
+
+    home.countdown()
+        .arg("compact")
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with("Compacted 3 events to 2,"));
+    golden("store-after-compact.toml", home.read_store().as_bytes());
+}
+
+#[test]
+fn batch_applies_every_command_or_none() {
+    let home = Home::new();
+
+    home.countdown()
+        .args(["batch", "-"])
+        .write_stdin("add-event -e Launch -d 1-3-2090\ndefer Lunch -u 1-1-2095\n")
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("Nothing was saved"));
+    assert!(!home.store().exists());
+
+    home.countdown()
+        .args(["batch", "-"])
+        .write_stdin(
+            "# fixture\nadd-event -e Launch -d 1-3-2090\n\nadd-event -e Conference -d 15-1-2090\n",
+        )
+        .assert()
+        .success()
+        .stdout(predicates::str::ends_with("Applied 2 commands\n"));
+    golden("store-after-add.toml", home.read_store().as_bytes());
+}
+
+#[test]
+fn batch_refuses_events_file_on_a_line() {
+    let home = Home::new();
+    let other = home.dir.path().join("other.toml");
+    fs::write(&other, FIXTURE_STORE).unwrap();
+
+    home.countdown()
+        .args(["batch", "-"])
+        .write_stdin(format!(
+            "add-event -e Sneaky -d 1-1-2091 --events-file {}\n",
+            other.display()
+        ))
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains(
+            "--events-file can only be given to countdown itself",
+        ));
+    assert!(!home.store().exists());
+    assert_eq!(fs::read_to_string(&other).unwrap(), FIXTURE_STORE);
+}
+
+#[cfg(feature = "shell")]
+#[test]
+fn shell_saves_once_on_exit() {
+    let home = Home::new();
+
+    home.countdown()
+        .arg("shell")
+        .write_stdin(
+            "add-event -e Launch -d 1-3-2090\nadd-event -e Conference -d 15-1-2090\nexit\n",
+        )
+        .assert()
+        .success()
+        .stdout(predicates::str::ends_with("Saved!\n"));
+    golden("store-after-add.toml", home.read_store().as_bytes());
+}
+
+#[test]
+fn doctor_reports_problems_with_fixes() {
+    let home = Home::with_fixture();
+
+    home.countdown().arg("doctor").assert().success().stdout(
+        predicates::str::contains("ok    store").and(predicates::str::contains("(2 events)")),
+    );
+
+    fs::write(home.store(), "[[events]]\nname = 'x'\n").unwrap();
+    fs::write(
+        home.config_dir().join("federate.toml"),
+        "[[sources]]\nname = 'team'\npath = '/nonexistent/team.toml'\n",
+    )
+    .unwrap();
+    home.countdown().arg("doctor").assert().code(2).stdout(
+        predicates::str::contains("FAIL  store")
+            .and(predicates::str::contains(
+                "FAIL  federation  can't read team",
+            ))
+            .and(predicates::str::contains("fix: ")),
+    );
+}
+
+#[test]
+fn failed_snapshot_still_reports_the_save() {
+    let home = Home::new();
+    fs::create_dir_all(home.config_dir()).unwrap();
+    fs::write(home.snapshots(), "not a directory").unwrap();
+
+    home.countdown()
+        .args(["add-event", "-e", "Launch", "-d", "1-3-2090"])
+        .assert()
+        .success()
+        .stdout("Added!\n")
+        .stderr(predicates::str::starts_with(
+            "Saved, but no snapshot was taken",
+        ));
+    home.countdown()
+        .args(["batch", "-"])
+        .write_stdin("add-event -e Conference -d 15-1-2090\n")
+        .assert()
+        .success()
+        .stdout("Added!\nApplied 1 commands\n");
+
+    golden("store-after-add.toml", home.read_store().as_bytes());
+}
+
+#[cfg(not(feature = "shell"))]
+#[test]
+fn shell_reports_when_it_is_not_built_in() {
+    let home = Home::new();
+
+    home.countdown()
+        .arg("shell")
+        .assert()
+        .code(2)
+        .stderr("shell isn't available, as countdown was built without the `shell` feature\n");
+}
+
+#[test]
+fn at_refuses_commands_that_write() {
+    let home = Home::with_fixture();
+
+    home.countdown()
+        .args(["at", FIXTURE_DATE, "add-event", "-e", "x", "-d", "1-1-2091"])
+        .assert()
+        .code(2)
+        .stderr("Only listing, alert and usage-report can be run at a past date\n");
+    assert_eq!(home.read_store(), FIXTURE_STORE);
+}
+
+#[test]
+fn import_freeform_adds_accepted_lines() {
+    let home = Home::new();
+    let notes = home.dir.path().join("notes.txt");
+    fs::write(
+        &notes,
+        "Launch 01-03-2090\nskip me 2090-02-01\nConference: 15/01/2090\n",
+    )
+    .unwrap();
+
+    home.countdown()
+        .args(["import", "--freeform"])
+        .arg(&notes)
+        .write_stdin("y\nn\ny\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::ends_with("Imported 2 events\n"));
+    golden("store-after-add.toml", home.read_store().as_bytes());
+}
+
+#[test]
+fn events_file_is_read_only_and_strict() {
+    let home = Home::with_fixture();
+    let events_file = home.store();
+
+    home.countdown()
+        .env("HOME", "/nonexistent")
+        .arg("--events-file")
+        .arg(&events_file)
+        .args(["add-event", "-e", "x", "-d", "1-1-2091"])
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("is read-only"));
+
+    fs::write(&events_file, "[[events]]\nname = 'x'\ntiem = 1\n").unwrap();
+    home.countdown()
+        .arg("--events-file")
+        .arg(&events_file)
+        .assert()
+        .code(2)
+        .stderr(predicates::str::contains("unknown field `tiem`"));
+}
+
+#[test]
+fn home_store_ignores_unknown_fields() {
+    let home = Home::new();
+    fs::create_dir_all(home.config_dir()).unwrap();
+    fs::write(
+        home.store(),
+        "[[events]]\nname = 'Launch'\ntime = 3792009600\ncolour = 'red'\n",
+    )
+    .unwrap();
+
+    home.countdown()
+        .assert()
+        .success()
+        .stdout(predicates::str::ends_with("days until Launch\n"));
+}
+
+#[test]
+fn federate_labels_events_by_source() {
+    let home = Home::with_fixture();
+    let team = home.dir.path().join("team.toml");
+    fs::write(&team, "[[events]]\nname = 'Offsite'\ntime = 3989347200\n").unwrap();
+    fs::write(
+        home.config_dir().join("federate.toml"),
+        format!("[[sources]]\nname = 'team'\npath = '{}'\n", team.display()),
+    )
+    .unwrap();
+
+    let listing = String::from_utf8(stdout_of(home.countdown().arg("federate"))).unwrap();
+    assert!(listing.ends_with("days until Offsite (team)\n"));
+
+    let listing = String::from_utf8(stdout_of(home.countdown().args([
+        "federate",
+        "--disable",
+        "personal",
+    ])))
+    .unwrap();
+    assert_eq!(listing.lines().count(), 1);
+    fs::remove_file(&team).unwrap();
+    home.countdown()
+        .arg("federate")
+        .assert()
+        .code(2)
+        .stderr(format!("Source 'team': {} is not a file\n", team.display()));
+}